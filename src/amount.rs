@@ -0,0 +1,170 @@
+//! Shared parsing for ledger posting amounts (sign, decimal, commodity).
+//!
+//! Amounts are kept as integer minor-units rather than floats so that
+//! balance checks and running totals don't drift from rounding.
+
+/// Number of fractional digits every parsed amount is normalized to.
+const SCALE: u32 = 4;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Amount {
+    pub commodity: String,
+    /// Value scaled by 10^SCALE, e.g. "$12.50" -> 125000 for commodity "$".
+    pub minor_units: i64,
+}
+
+/// Parses a posting amount like `$12.00`, `-$5`, `12.50 USD` or `-3.25 BTC`.
+///
+/// Returns `None` if `text` doesn't contain a recognizable number or has no
+/// commodity (a bare `12.00` is not parsed).
+pub fn parse_amount(text: &str) -> Option<Amount> {
+    let text = text.trim();
+    if text.is_empty() {
+        return None;
+    }
+
+    let mut sign = 1i64;
+    let mut rest = text;
+    if let Some(stripped) = rest.strip_prefix('-') {
+        sign = -1;
+        rest = stripped.trim_start();
+    } else if let Some(stripped) = rest.strip_prefix('+') {
+        rest = stripped.trim_start();
+    }
+
+    // Leading commodity symbol, e.g. "$12.00" or "$-12.00".
+    let (symbol_prefix, rest) = split_leading_symbol(rest);
+    let mut rest = rest;
+    // The sign can come before or after the symbol ("-$12.00" or "$-12.00"),
+    // so check again here regardless of whether a symbol was found above.
+    if let Some(stripped) = rest.strip_prefix('-') {
+        sign = -1;
+        rest = stripped.trim_start();
+    }
+
+    let (number, trailing) = split_number(rest)?;
+    let minor_units = sign * parse_decimal_to_minor_units(number)?;
+
+    let commodity = match symbol_prefix {
+        Some(symbol) => symbol,
+        None => trailing.trim().to_string(),
+    };
+    if commodity.is_empty() {
+        return None;
+    }
+
+    Some(Amount {
+        commodity,
+        minor_units,
+    })
+}
+
+/// Splits off a leading non-alphanumeric, non-whitespace commodity symbol
+/// (e.g. `$`, `€`) from the front of `text`.
+fn split_leading_symbol(text: &str) -> (Option<String>, &str) {
+    let mut chars = text.char_indices();
+    match chars.next() {
+        Some((_, c)) if !c.is_alphanumeric() && c != '-' && c != '+' && !c.is_whitespace() => {
+            let end = chars.next().map(|(i, _)| i).unwrap_or(text.len());
+            (Some(text[..end].to_string()), &text[end..])
+        }
+        _ => (None, text),
+    }
+}
+
+/// Splits the leading numeric literal (digits, `.` or `,` separators) from
+/// the remainder of the string, returning `(number, rest)`.
+fn split_number(text: &str) -> Option<(&str, &str)> {
+    let end = text
+        .char_indices()
+        .find(|(_, c)| !(c.is_ascii_digit() || *c == '.' || *c == ','))
+        .map(|(i, _)| i)
+        .unwrap_or(text.len());
+    if end == 0 {
+        None
+    } else {
+        Some((&text[..end], &text[end..]))
+    }
+}
+
+/// Parses a plain decimal literal (commas as thousands separators are
+/// ignored) into minor units scaled by [`SCALE`].
+fn parse_decimal_to_minor_units(number: &str) -> Option<i64> {
+    let number: String = number.chars().filter(|c| *c != ',').collect();
+    let (whole, frac) = match number.split_once('.') {
+        Some((w, f)) => (w, f),
+        None => (number.as_str(), ""),
+    };
+    let whole: i64 = if whole.is_empty() { 0 } else { whole.parse().ok()? };
+    let mut frac = frac.to_string();
+    if frac.len() > SCALE as usize {
+        frac.truncate(SCALE as usize);
+    }
+    while frac.len() < SCALE as usize {
+        frac.push('0');
+    }
+    let frac: i64 = if frac.is_empty() { 0 } else { frac.parse().ok()? };
+    Some(whole * 10i64.pow(SCALE) + frac)
+}
+
+/// Formats minor units back into a human-readable decimal string for
+/// diagnostics and inlay hints, e.g. `125000 -> "12.5000"`.
+pub fn format_minor_units(minor_units: i64) -> String {
+    let sign = if minor_units < 0 { "-" } else { "" };
+    let abs = minor_units.unsigned_abs();
+    let divisor = 10u64.pow(SCALE);
+    let whole = abs / divisor;
+    let frac = abs % divisor;
+    format!("{sign}{whole}.{frac:0width$}", width = SCALE as usize)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parses_leading_symbol() {
+        assert_eq!(
+            parse_amount("$12.00"),
+            Some(Amount {
+                commodity: "$".to_string(),
+                minor_units: 120000,
+            })
+        );
+    }
+
+    #[test]
+    fn parses_negative_leading_symbol() {
+        assert_eq!(
+            parse_amount("-$12.00"),
+            Some(Amount {
+                commodity: "$".to_string(),
+                minor_units: -120000,
+            })
+        );
+        assert_eq!(
+            parse_amount("$-12.00"),
+            Some(Amount {
+                commodity: "$".to_string(),
+                minor_units: -120000,
+            })
+        );
+    }
+
+    #[test]
+    fn parses_trailing_ticker() {
+        assert_eq!(
+            parse_amount("-3.25 BTC"),
+            Some(Amount {
+                commodity: "BTC".to_string(),
+                minor_units: -32500,
+            })
+        );
+    }
+
+    #[test]
+    fn formats_minor_units() {
+        assert_eq!(format_minor_units(120000), "12.0000");
+        assert_eq!(format_minor_units(-32500), "-3.2500");
+    }
+}
@@ -1,78 +1,734 @@
-use std::collections::HashSet;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
 
-use tower_lsp::lsp_types::Position;
+use tower_lsp::lsp_types::{
+    Diagnostic, DiagnosticSeverity, DocumentSymbol, FoldingRange, FoldingRangeKind, InlayHint,
+    InlayHintKind, InlayHintLabel, Position, Range, SelectionRange, SymbolKind,
+    TextDocumentContentChangeEvent, Url,
+};
 use tracing::debug;
-use tree_sitter::{Node, Parser, Point, Tree};
+use tree_sitter::{InputEdit, Node, Parser, Point, Tree};
 
+use crate::amount::{format_minor_units, parse_amount};
+
+struct Document {
+    source: String,
+    ast: Option<Tree>,
+}
+
+/// Holds every file loaded into the workspace, keyed by its `Url`: the
+/// document(s) opened by the editor plus anything pulled in transitively
+/// through `include` directives. A `BTreeMap` keeps iteration order stable
+/// (sorted by `Url`) so features like go-to-definition pick the same
+/// candidate across runs when more than one file matches.
 pub struct Ledger {
     parser: Parser,
-    ast: Option<Tree>,
-    source: String,
+    documents: BTreeMap<Url, Document>,
+    /// Files opened directly by the editor; never evicted by include
+    /// rescanning, unlike documents pulled in only via `include`.
+    open_documents: HashSet<Url>,
 }
 
 impl Ledger {
     pub fn new(parser: Parser) -> Self {
         Self {
             parser,
-            ast: None,
-            source: "".to_string(),
+            documents: BTreeMap::new(),
+            open_documents: HashSet::new(),
+        }
+    }
+
+    /// Fully (re)parses `text` for `uri`, discarding any previous tree for
+    /// that file, then resolves and loads its `include` directives.
+    pub fn process_text(&mut self, uri: Url, text: &str) {
+        let ast = self.parser.parse(text, None);
+        self.documents.insert(
+            uri.clone(),
+            Document {
+                source: text.to_string(),
+                ast,
+            },
+        );
+        self.open_documents.insert(uri);
+        self.load_includes();
+    }
+
+    /// Applies a single `TextDocumentContentChangeEvent` to `uri`, editing
+    /// the existing tree in place when the change carries a range so
+    /// tree-sitter can reuse unchanged subtrees, and falling back to a full
+    /// reparse otherwise.
+    pub fn apply_change(&mut self, uri: &Url, change: &TextDocumentContentChangeEvent) {
+        match change.range {
+            Some(range) => {
+                self.apply_incremental_change(uri, range, &change.text);
+                self.load_includes();
+            }
+            None => self.process_text(uri.clone(), &change.text),
+        }
+    }
+
+    /// Marks `uri` as no longer open in the editor and rescans includes, so
+    /// the document is evicted unless it's still reachable from some other
+    /// open document's `include` chain.
+    pub fn close_document(&mut self, uri: &Url) {
+        self.open_documents.remove(uri);
+        self.load_includes();
+    }
+
+    fn apply_incremental_change(&mut self, uri: &Url, range: Range, text: &str) {
+        let Some(doc) = self.documents.get_mut(uri) else {
+            return;
+        };
+
+        let start_byte = position_to_byte(&doc.source, range.start);
+        let old_end_byte = position_to_byte(&doc.source, range.end);
+
+        let mut new_source =
+            String::with_capacity(doc.source.len() - (old_end_byte - start_byte) + text.len());
+        new_source.push_str(&doc.source[..start_byte]);
+        new_source.push_str(text);
+        new_source.push_str(&doc.source[old_end_byte..]);
+
+        let new_end_byte = start_byte + text.len();
+
+        if let Some(tree) = doc.ast.as_mut() {
+            tree.edit(&InputEdit {
+                start_byte,
+                old_end_byte,
+                new_end_byte,
+                start_position: position_to_point(range.start),
+                old_end_position: position_to_point(range.end),
+                new_end_position: byte_to_point(&new_source, new_end_byte),
+            });
         }
+
+        doc.ast = self.parser.parse(&new_source, doc.ast.as_ref());
+        doc.source = new_source;
     }
 
-    pub fn process_text(&mut self, s: &String) {
-        let mut parser = tree_sitter::Parser::new();
-        _ = parser.set_language(tree_sitter_ledger::language());
-        self.ast = parser.parse(&s, None).unwrap().into();
-        self.source = s.clone()
+    /// Recomputes the full set of files reachable from the currently open
+    /// documents by walking `include` directives transitively, parsing any
+    /// newly-discovered file, and evicting previously include-loaded
+    /// documents that are no longer reachable (e.g. an `include` line was
+    /// deleted or edited to point elsewhere). Editor-opened documents are
+    /// never evicted, even if no longer referenced by any include.
+    fn load_includes(&mut self) {
+        let mut reachable: HashSet<Url> = self.open_documents.clone();
+        let mut frontier: Vec<Url> = self.open_documents.iter().cloned().collect();
+
+        while let Some(current) = frontier.pop() {
+            let mut include_paths = vec![];
+            if let Some(doc) = self.documents.get(&current) {
+                if let Some(ast) = doc.ast.as_ref() {
+                    traverse(ast.root_node(), &mut |node| {
+                        if node.kind() == "include" {
+                            if let Some(path) = include_path(node, &doc.source) {
+                                include_paths.push(path);
+                            }
+                        }
+                    });
+                }
+            }
+
+            for path in include_paths {
+                let Ok(include_uri) = current.join(&path) else {
+                    continue;
+                };
+                if !self.documents.contains_key(&include_uri) {
+                    let Ok(file_path) = include_uri.to_file_path() else {
+                        continue;
+                    };
+                    let Ok(text) = fs::read_to_string(file_path) else {
+                        continue;
+                    };
+                    let ast = self.parser.parse(&text, None);
+                    self.documents
+                        .insert(include_uri.clone(), Document { source: text, ast });
+                }
+                if reachable.insert(include_uri.clone()) {
+                    frontier.push(include_uri);
+                }
+            }
+        }
+
+        self.documents.retain(|uri, _| reachable.contains(uri));
     }
 
-    pub fn get_accounts(&self, pos: Position) -> Vec<String> {
+    pub fn get_accounts(&self, uri: &Url, pos: Position) -> Vec<String> {
         let mut accounts: HashSet<String> = HashSet::new();
-        debug!("get_accounts: pre {:?}", accounts);
-        traverse(
-            self.ast.as_ref().expect("").root_node(),
-            &mut |node: Node| {
+        for (doc_uri, doc) in &self.documents {
+            let Some(ast) = doc.ast.as_ref() else {
+                continue;
+            };
+            traverse(ast.root_node(), &mut |node: Node| {
                 if node.kind() == "account"
-                    && !in_range(pos, node.start_position(), node.end_position())
+                    && !(doc_uri == uri && in_range(pos, node.start_position(), node.end_position()))
                 {
-                    debug!(
-                        "get_accounts: in {:?} ({:?}, {:?})",
-                        self.source[node.start_byte()..node.end_byte()].to_string(),
-                        node.start_position(),
-                        node.end_position()
-                    );
-                    accounts.insert(self.source[node.start_byte()..node.end_byte()].into());
+                    accounts.insert(doc.source[node.byte_range()].to_string());
                 }
-            },
-        );
-        debug!("get_accounts: post {:?}", accounts);
+            });
+        }
+        debug!("get_accounts: {:?}", accounts);
         accounts.into_iter().collect()
     }
 
-    pub fn get_payees(&self, pos: Position) -> Vec<String> {
+    pub fn get_payees(&self, uri: &Url, pos: Position) -> Vec<String> {
         let mut payees: HashSet<String> = HashSet::new();
-        traverse(
-            self.ast.as_ref().expect("").root_node(),
-            &mut |node: Node| {
+        for (doc_uri, doc) in &self.documents {
+            let Some(ast) = doc.ast.as_ref() else {
+                continue;
+            };
+            traverse(ast.root_node(), &mut |node: Node| {
                 if node.kind() == "payee"
-                    && !in_range(pos, node.start_position(), node.end_position())
+                    && !(doc_uri == uri && in_range(pos, node.start_position(), node.end_position()))
                 {
-                    payees.insert(self.source[node.start_byte()..node.end_byte()].into());
+                    payees.insert(doc.source[node.byte_range()].to_string());
                 }
-            },
-        );
+            });
+        }
         payees.into_iter().collect()
     }
 
-    pub fn traverse_ast(&self, f: &mut impl FnMut(Node)) {
-        traverse(
-            self.ast
-                .as_ref()
-                .expect("tree should be present")
-                .root_node(),
-            f,
-        );
+    pub fn traverse_ast(&self, uri: &Url, f: &mut impl FnMut(Node)) {
+        let Some(doc) = self.documents.get(uri) else {
+            return;
+        };
+        let Some(ast) = doc.ast.as_ref() else {
+            return;
+        };
+        traverse(ast.root_node(), f);
     }
+
+    /// Verifies double-entry balance for every transaction in `uri`,
+    /// returning one `Diagnostic` per unbalanced or ambiguous transaction.
+    pub fn compute_diagnostics(&self, uri: &Url) -> Vec<Diagnostic> {
+        let mut diagnostics = vec![];
+        let Some(doc) = self.documents.get(uri) else {
+            return diagnostics;
+        };
+        let Some(ast) = doc.ast.as_ref() else {
+            return diagnostics;
+        };
+
+        traverse(ast.root_node(), &mut |node| {
+            if !is_transaction_node(node) {
+                return;
+            }
+            if let Some(diagnostic) = check_transaction_balance(node, &doc.source) {
+                diagnostics.push(diagnostic);
+            }
+        });
+
+        diagnostics
+    }
+
+    /// Computes inlay hints for elided posting amounts and, for accounts
+    /// that recur in `uri`, a running balance after each posting.
+    pub fn compute_inlay_hints(&self, uri: &Url) -> Vec<InlayHint> {
+        let mut hints = vec![];
+        let Some(doc) = self.documents.get(uri) else {
+            return hints;
+        };
+        let Some(ast) = doc.ast.as_ref() else {
+            return hints;
+        };
+
+        let mut account_counts: HashMap<String, usize> = HashMap::new();
+        traverse(ast.root_node(), &mut |node| {
+            if node.kind() == "posting" {
+                if let Some(account) = find_child_by_kind(node, "account") {
+                    *account_counts
+                        .entry(doc.source[account.byte_range()].to_string())
+                        .or_insert(0) += 1;
+                }
+            }
+        });
+
+        let mut running_balances: HashMap<String, HashMap<String, i64>> = HashMap::new();
+        traverse(ast.root_node(), &mut |node| {
+            if !is_transaction_node(node) {
+                return;
+            }
+            collect_transaction_hints(
+                node,
+                &doc.source,
+                &account_counts,
+                &mut running_balances,
+                &mut hints,
+            );
+        });
+
+        hints
+    }
+
+    /// Builds a hierarchical outline for `uri`: transactions with their
+    /// postings nested underneath, alongside top-level directives.
+    pub fn compute_document_symbols(&self, uri: &Url) -> Vec<DocumentSymbol> {
+        let Some(doc) = self.documents.get(uri) else {
+            return vec![];
+        };
+        let Some(ast) = doc.ast.as_ref() else {
+            return vec![];
+        };
+        let mut cursor = ast.root_node().walk();
+        ast.root_node()
+            .children(&mut cursor)
+            .filter_map(|node| top_level_symbol(node, &doc.source))
+            .collect()
+    }
+
+    /// Emits one `FoldingRange` per multi-line transaction in `uri` so
+    /// editors can collapse individual entries.
+    pub fn compute_folding_ranges(&self, uri: &Url) -> Vec<FoldingRange> {
+        let mut ranges = vec![];
+        let Some(doc) = self.documents.get(uri) else {
+            return ranges;
+        };
+        let Some(ast) = doc.ast.as_ref() else {
+            return ranges;
+        };
+
+        traverse(ast.root_node(), &mut |node| {
+            if is_transaction_node(node) && node.end_position().row > node.start_position().row {
+                ranges.push(FoldingRange {
+                    start_line: node.start_position().row as u32,
+                    start_character: None,
+                    end_line: node.end_position().row as u32,
+                    end_character: None,
+                    kind: Some(FoldingRangeKind::Region),
+                    collapsed_text: None,
+                });
+            }
+        });
+
+        ranges
+    }
+
+    /// Finds every occurrence, across every loaded file, of the account or
+    /// payee under `pos` in `uri` — the same exact-text node matching
+    /// `rename` uses, exposed for reuse and for cross-file references.
+    pub fn find_occurrences(&self, uri: &Url, pos: Position) -> Vec<(Url, Range)> {
+        let mut results = vec![];
+        let Some(doc) = self.documents.get(uri) else {
+            return results;
+        };
+        let Some(ast) = doc.ast.as_ref() else {
+            return results;
+        };
+        let Some(cur_node) = node_at(ast.root_node(), pos) else {
+            return results;
+        };
+        if !matches!(cur_node.kind(), "account" | "payee") {
+            return results;
+        }
+        let kind = cur_node.kind();
+        let cur_text = doc.source[cur_node.byte_range()].to_string();
+
+        for (doc_uri, doc) in &self.documents {
+            let Some(ast) = doc.ast.as_ref() else {
+                continue;
+            };
+            traverse(ast.root_node(), &mut |node| {
+                if node.kind() == kind && doc.source[node.byte_range()] == cur_text {
+                    results.push((doc_uri.clone(), node_range_inclusive(node)));
+                }
+            });
+        }
+        results
+    }
+
+    /// Resolves go-to-definition for an `account` node at `pos` in `uri`:
+    /// the matching top-level `account` directive anywhere in the
+    /// workspace if one is declared, otherwise the first posting that uses
+    /// the account.
+    pub fn find_definition(&self, uri: &Url, pos: Position) -> Option<(Url, Range)> {
+        let doc = self.documents.get(uri)?;
+        let ast = doc.ast.as_ref()?;
+        let cur_node = node_at(ast.root_node(), pos)?;
+        if cur_node.kind() != "account" {
+            return None;
+        }
+        let cur_text = doc.source[cur_node.byte_range()].to_string();
+
+        for (doc_uri, doc) in &self.documents {
+            let Some(ast) = doc.ast.as_ref() else {
+                continue;
+            };
+            let mut cursor = ast.root_node().walk();
+            let declaration = ast
+                .root_node()
+                .children(&mut cursor)
+                .find(|n| n.kind() == "account" && doc.source[n.byte_range()] == cur_text);
+            if let Some(declaration) = declaration {
+                return Some((doc_uri.clone(), node_range_inclusive(declaration)));
+            }
+        }
+
+        for (doc_uri, doc) in &self.documents {
+            let Some(ast) = doc.ast.as_ref() else {
+                continue;
+            };
+            let mut first_use = None;
+            traverse(ast.root_node(), &mut |node| {
+                if first_use.is_none()
+                    && node.kind() == "account"
+                    && doc.source[node.byte_range()] == cur_text
+                {
+                    first_use = Some(node_range_inclusive(node));
+                }
+            });
+            if let Some(range) = first_use {
+                return Some((doc_uri.clone(), range));
+            }
+        }
+        None
+    }
+
+    /// Finds the renameable account or payee node under `pos` in `uri`,
+    /// returning its range and current text for `prepare_rename`.
+    pub fn find_rename_target(&self, uri: &Url, pos: Position) -> Option<(Range, String)> {
+        let doc = self.documents.get(uri)?;
+        let ast = doc.ast.as_ref()?;
+        let node = node_at(ast.root_node(), pos)?;
+        if !matches!(node.kind(), "account" | "payee") {
+            return None;
+        }
+        Some((
+            node_range_inclusive(node),
+            doc.source[node.byte_range()].to_string(),
+        ))
+    }
+
+    /// Builds a chain of nested `SelectionRange`s for each requested
+    /// position in `uri`, from the smallest named node containing it
+    /// outward through its ancestors to the file root.
+    pub fn compute_selection_ranges(&self, uri: &Url, positions: &[Position]) -> Vec<SelectionRange> {
+        let Some(doc) = self.documents.get(uri) else {
+            return vec![];
+        };
+        let Some(ast) = doc.ast.as_ref() else {
+            return vec![];
+        };
+        positions
+            .iter()
+            .map(|pos| match node_at(ast.root_node(), *pos) {
+                Some(node) => selection_range_chain(node),
+                None => SelectionRange {
+                    range: Range::new(*pos, *pos),
+                    parent: None,
+                },
+            })
+            .collect()
+    }
+}
+
+/// Extracts the quoted path out of an `include "path"` directive node.
+fn include_path(node: Node, source: &str) -> Option<String> {
+    let text = source[node.byte_range()].trim();
+    let text = text.strip_prefix("include").unwrap_or(text).trim();
+    let path = text.trim_matches(|c| c == '"' || c == '\'');
+    if path.is_empty() {
+        None
+    } else {
+        Some(path.to_string())
+    }
+}
+
+fn check_transaction_balance(xact: Node, source: &str) -> Option<Diagnostic> {
+    let header_range = transaction_header_range(xact, source);
+    let mut totals: HashMap<String, i64> = HashMap::new();
+    let mut elided = 0;
+
+    let mut cursor = xact.walk();
+    for posting in xact.children(&mut cursor).filter(|n| n.kind() == "posting") {
+        match find_child_by_kind(posting, "amount") {
+            Some(amount_node) => {
+                let text = &source[amount_node.byte_range()];
+                if let Some(amount) = parse_amount(text) {
+                    *totals.entry(amount.commodity).or_insert(0) += amount.minor_units;
+                }
+            }
+            None => elided += 1,
+        }
+    }
+
+    if elided >= 2 {
+        return Some(Diagnostic {
+            range: header_range,
+            severity: Some(DiagnosticSeverity::ERROR),
+            message: "Transaction has more than one posting with an elided amount; \
+                      the balance cannot be resolved"
+                .to_string(),
+            ..Default::default()
+        });
+    }
+    if elided == 1 {
+        // A single elided posting always absorbs whatever is left over.
+        return None;
+    }
+
+    let residuals: Vec<String> = totals
+        .into_iter()
+        .filter(|(_, total)| *total != 0)
+        .map(|(commodity, total)| format!("{commodity}{}", format_minor_units(total)))
+        .collect();
+
+    if residuals.is_empty() {
+        return None;
+    }
+
+    Some(Diagnostic {
+        range: header_range,
+        severity: Some(DiagnosticSeverity::ERROR),
+        message: format!(
+            "Transaction does not balance, residual: {}",
+            residuals.join(", ")
+        ),
+        ..Default::default()
+    })
+}
+
+/// Range covering just the transaction's header line (its date/payee), not
+/// the postings underneath, so unbalanced-transaction diagnostics underline
+/// the entry's heading rather than the whole multi-line transaction.
+fn transaction_header_range(xact: Node, source: &str) -> Range {
+    let start_byte = xact.start_byte();
+    let header_end_byte = source[start_byte..]
+        .find('\n')
+        .map(|offset| start_byte + offset)
+        .unwrap_or_else(|| xact.end_byte());
+    Range::new(
+        point_to_position(xact.start_position()),
+        point_to_position(byte_to_point(source, header_end_byte)),
+    )
+}
+
+fn collect_transaction_hints(
+    xact: Node,
+    source: &str,
+    account_counts: &HashMap<String, usize>,
+    running_balances: &mut HashMap<String, HashMap<String, i64>>,
+    hints: &mut Vec<InlayHint>,
+) {
+    let mut cursor = xact.walk();
+    let postings: Vec<Node> = xact
+        .children(&mut cursor)
+        .filter(|n| n.kind() == "posting")
+        .collect();
+
+    let mut totals: HashMap<String, i64> = HashMap::new();
+    let mut elided_postings: Vec<Node> = vec![];
+    for posting in &postings {
+        match find_child_by_kind(*posting, "amount") {
+            Some(amount_node) => {
+                if let Some(amount) = parse_amount(&source[amount_node.byte_range()]) {
+                    *totals.entry(amount.commodity).or_insert(0) += amount.minor_units;
+                }
+            }
+            None => elided_postings.push(*posting),
+        }
+    }
+
+    if elided_postings.len() == 1 {
+        let label = totals
+            .iter()
+            .filter(|(_, total)| **total != 0)
+            .map(|(commodity, total)| format!("{commodity}{}", format_minor_units(-total)))
+            .collect::<Vec<_>>()
+            .join(", ");
+        if !label.is_empty() {
+            hints.push(inlay_hint(point_to_position(elided_postings[0].end_position()), label));
+        }
+    }
+
+    for posting in &postings {
+        let Some(account) = find_child_by_kind(*posting, "account") else {
+            continue;
+        };
+        let account = source[account.byte_range()].to_string();
+        if account_counts.get(&account).copied().unwrap_or(0) < 2 {
+            continue;
+        }
+        let Some(amount_node) = find_child_by_kind(*posting, "amount") else {
+            continue;
+        };
+        let Some(amount) = parse_amount(&source[amount_node.byte_range()]) else {
+            continue;
+        };
+
+        let balances = running_balances.entry(account).or_default();
+        *balances.entry(amount.commodity).or_insert(0) += amount.minor_units;
+        let label = balances
+            .iter()
+            .map(|(commodity, total)| format!("{commodity}{}", format_minor_units(*total)))
+            .collect::<Vec<_>>()
+            .join(", ");
+        hints.push(inlay_hint(point_to_position(posting.end_position()), format!("= {label}")));
+    }
+}
+
+fn top_level_symbol(node: Node, source: &str) -> Option<DocumentSymbol> {
+    match node.kind() {
+        "xact" | "transaction" => Some(transaction_symbol(node, source)),
+        "account" => Some(document_symbol(text(node, source), SymbolKind::NAMESPACE, node)),
+        "commodity" => Some(document_symbol(text(node, source), SymbolKind::CONSTANT, node)),
+        "include" => Some(document_symbol(text(node, source), SymbolKind::FILE, node)),
+        _ => None,
+    }
+}
+
+fn transaction_symbol(xact: Node, source: &str) -> DocumentSymbol {
+    let date = find_child_by_kind(xact, "date").map(|n| text(n, source));
+    let payee = find_child_by_kind(xact, "payee").map(|n| text(n, source));
+    let name = match (date, payee) {
+        (Some(date), Some(payee)) => format!("{date} {payee}"),
+        (Some(date), None) => date,
+        (None, Some(payee)) => payee,
+        (None, None) => text(xact, source),
+    };
+
+    let mut cursor = xact.walk();
+    let postings = xact
+        .children(&mut cursor)
+        .filter(|n| n.kind() == "posting")
+        .map(|posting| {
+            let account = find_child_by_kind(posting, "account")
+                .map(|n| text(n, source))
+                .unwrap_or_else(|| text(posting, source));
+            document_symbol(account, SymbolKind::FIELD, posting)
+        })
+        .collect();
+
+    document_symbol_with_children(name, SymbolKind::EVENT, xact, postings)
+}
+
+fn text(node: Node, source: &str) -> String {
+    source[node.byte_range()].to_string()
+}
+
+fn selection_range_chain(node: Node) -> SelectionRange {
+    let mut ancestors = vec![];
+    let mut current = Some(node);
+    while let Some(n) = current {
+        if n.is_named() {
+            ancestors.push(n);
+        }
+        current = n.parent();
+    }
+
+    let mut selection_range = None;
+    for ancestor in ancestors.into_iter().rev() {
+        selection_range = Some(SelectionRange {
+            range: node_range(ancestor),
+            parent: selection_range.map(Box::new),
+        });
+    }
+    selection_range.expect("named_descendant_for_point_range always yields a named node")
+}
+
+fn node_at(root: Node, pos: Position) -> Option<Node> {
+    let point = position_to_point(pos);
+    root.named_descendant_for_point_range(point, point)
+}
+
+/// Matches the `rename`/`prepare_rename` convention of an inclusive end
+/// column, so references line up with the range they already advertise.
+fn node_range_inclusive(node: Node) -> Range {
+    Range::new(
+        point_to_position(node.start_position()),
+        Position {
+            line: node.end_position().row as u32,
+            character: node.end_position().column as u32 + 1,
+        },
+    )
+}
+
+fn document_symbol(name: String, kind: SymbolKind, node: Node) -> DocumentSymbol {
+    document_symbol_with_children(name, kind, node, vec![])
+}
+
+#[allow(deprecated)]
+fn document_symbol_with_children(
+    name: String,
+    kind: SymbolKind,
+    node: Node,
+    children: Vec<DocumentSymbol>,
+) -> DocumentSymbol {
+    let range = node_range(node);
+    DocumentSymbol {
+        name,
+        detail: None,
+        kind,
+        tags: None,
+        deprecated: None,
+        range,
+        selection_range: range,
+        children: if children.is_empty() {
+            None
+        } else {
+            Some(children)
+        },
+    }
+}
+
+fn inlay_hint(position: Position, label: String) -> InlayHint {
+    InlayHint {
+        position,
+        label: InlayHintLabel::String(label),
+        kind: Some(InlayHintKind::TYPE),
+        text_edits: None,
+        tooltip: None,
+        padding_left: Some(true),
+        padding_right: None,
+        data: None,
+    }
+}
+
+fn is_transaction_node(node: Node) -> bool {
+    matches!(node.kind(), "xact" | "transaction")
+}
+
+fn find_child_by_kind<'a>(node: Node<'a>, kind: &str) -> Option<Node<'a>> {
+    let mut cursor = node.walk();
+    node.children(&mut cursor).find(|n| n.kind() == kind)
+}
+
+pub fn point_to_position(point: Point) -> Position {
+    Position {
+        line: point.row as u32,
+        character: point.column as u32,
+    }
+}
+
+fn position_to_point(pos: Position) -> Point {
+    Point::new(pos.line as usize, pos.character as usize)
+}
+
+/// Converts a line/column `Position` into a byte offset into `source`.
+fn position_to_byte(source: &str, pos: Position) -> usize {
+    let mut offset = 0;
+    for (i, line) in source.split('\n').enumerate() {
+        if i == pos.line as usize {
+            return offset + (pos.character as usize).min(line.len());
+        }
+        offset += line.len() + 1;
+    }
+    source.len()
+}
+
+/// Converts a byte offset into `source` into a tree-sitter `Point`.
+fn byte_to_point(source: &str, byte: usize) -> Point {
+    let prefix = &source[..byte];
+    let row = prefix.matches('\n').count();
+    let line_start = prefix.rfind('\n').map(|i| i + 1).unwrap_or(0);
+    Point::new(row, byte - line_start)
+}
+
+pub fn node_range(node: Node) -> Range {
+    Range::new(
+        point_to_position(node.start_position()),
+        point_to_position(node.end_position()),
+    )
 }
 
 pub fn traverse(node: Node, f: &mut impl FnMut(Node)) {
@@ -93,11 +749,23 @@ pub fn in_range(pos: Position, start: Point, end: Point) -> bool {
 #[cfg(test)]
 mod test {
     use std::collections::HashSet;
+    use std::fs;
 
+    use tower_lsp::lsp_types::{Position, Range, TextDocumentContentChangeEvent, Url};
     use tree_sitter::Parser;
 
     use crate::ledger::Ledger;
 
+    fn test_uri() -> Url {
+        Url::parse("file:///test.ledger").unwrap()
+    }
+
+    fn test_parser() -> Parser {
+        let mut parser = Parser::new();
+        parser.set_language(tree_sitter_ledger::language());
+        parser
+    }
+
     #[test]
     fn get_all_accounts() {
         // arrange
@@ -107,8 +775,9 @@ mod test {
         let mut ledger = Ledger::new(parser);
 
         // act
-        ledger.process_text(&s);
-        let a: HashSet<String> = HashSet::from_iter(ledger.get_accounts(Default::default()));
+        ledger.process_text(test_uri(), &s);
+        let a: HashSet<String> =
+            HashSet::from_iter(ledger.get_accounts(&test_uri(), Default::default()));
 
         // assert
         assert_eq!(
@@ -129,8 +798,9 @@ mod test {
         let mut ledger = Ledger::new(parser);
 
         // act
-        ledger.process_text(&s);
-        let a: HashSet<String> = HashSet::from_iter(ledger.get_payees(Default::default()));
+        ledger.process_text(test_uri(), &s);
+        let a: HashSet<String> =
+            HashSet::from_iter(ledger.get_payees(&test_uri(), Default::default()));
 
         // assert
         assert_eq!(
@@ -138,4 +808,96 @@ mod test {
             HashSet::from_iter::<Vec<String>>(vec!["Test Payerr".to_string(),])
         );
     }
+
+    #[test]
+    fn incremental_change_matches_full_reparse() {
+        // arrange: two ledgers starting from the same source, one edited
+        // incrementally and one fully reparsed, should end up with
+        // equivalent trees once the edit is applied. Both postings carry
+        // explicit (non-elided) amounts so a broken `InputEdit`/byte-offset
+        // miscalculation would actually surface as a different balance
+        // diagnostic rather than being masked by elided-posting handling.
+        let original =
+            "2025-01-01 Test Payerr\n\tExpenses:Dinner\t$12.00\n\tAssets:Wallet\t-$12.00\n"
+                .to_string();
+        let edited =
+            "2025-01-01 Test Payerr\n\tExpenses:Dinner\t$15.00\n\tAssets:Wallet\t-$12.00\n"
+                .to_string();
+
+        let mut incremental_ledger = Ledger::new(test_parser());
+        incremental_ledger.process_text(test_uri(), &original);
+        incremental_ledger.apply_change(
+            &test_uri(),
+            &TextDocumentContentChangeEvent {
+                range: Some(Range::new(Position::new(1, 18), Position::new(1, 23))),
+                range_length: None,
+                text: "15.00".to_string(),
+            },
+        );
+
+        let mut full_ledger = Ledger::new(test_parser());
+        full_ledger.process_text(test_uri(), &edited);
+
+        // act
+        let incremental_doc = incremental_ledger.documents.get(&test_uri()).unwrap();
+        let full_doc = full_ledger.documents.get(&test_uri()).unwrap();
+        let incremental_sexp = incremental_doc.ast.as_ref().unwrap().root_node().to_sexp();
+        let full_sexp = full_doc.ast.as_ref().unwrap().root_node().to_sexp();
+        let incremental_diagnostics = incremental_ledger.compute_diagnostics(&test_uri());
+        let full_diagnostics = full_ledger.compute_diagnostics(&test_uri());
+
+        // assert: the incrementally-edited tree is structurally identical
+        // to a full reparse of the edited source...
+        assert_eq!(incremental_sexp, full_sexp);
+        assert_eq!(incremental_doc.source, edited);
+        // ...and, because the edit made the transaction unbalanced, both
+        // report the same non-empty residual diagnostic rather than both
+        // trivially reporting nothing.
+        assert_eq!(incremental_diagnostics.len(), 1);
+        assert_eq!(incremental_diagnostics, full_diagnostics);
+        assert!(incremental_diagnostics[0].message.contains("3.0000"));
+    }
+
+    #[test]
+    fn aggregates_accounts_and_definitions_across_included_files() {
+        // arrange: a main file that includes an accounts file declaring
+        // `Assets:Wallet`, and itself uses that account in a transaction.
+        let dir = std::env::temp_dir().join(format!(
+            "ledger_ls_test_{:?}",
+            std::thread::current().id()
+        ));
+        fs::create_dir_all(&dir).unwrap();
+        let main_path = dir.join("main.ledger");
+        let included_path = dir.join("accounts.ledger");
+        fs::write(
+            &main_path,
+            "include \"accounts.ledger\"\n2025-01-01 Test Payerr\n\tAssets:Wallet\t$12.00\n\tExpenses:Dinner\n",
+        )
+        .unwrap();
+        fs::write(&included_path, "account Assets:Wallet\n").unwrap();
+
+        let main_uri = Url::from_file_path(&main_path).unwrap();
+        let included_uri = Url::from_file_path(&included_path).unwrap();
+
+        let mut ledger = Ledger::new(test_parser());
+
+        // act
+        ledger.process_text(main_uri.clone(), &fs::read_to_string(&main_path).unwrap());
+        let accounts: HashSet<String> =
+            HashSet::from_iter(ledger.get_accounts(&main_uri, Default::default()));
+        let wallet_pos = tower_lsp::lsp_types::Position::new(2, 2);
+        let definition = ledger.find_definition(&main_uri, wallet_pos);
+
+        // assert
+        assert_eq!(
+            accounts,
+            HashSet::from_iter::<Vec<String>>(vec![
+                "Assets:Wallet".to_string(),
+                "Expenses:Dinner".to_string(),
+            ])
+        );
+        assert_eq!(definition.map(|(uri, _)| uri), Some(included_uri));
+
+        fs::remove_dir_all(&dir).ok();
+    }
 }
@@ -1,3 +1,4 @@
+mod amount;
 mod ledger;
 
 use ls::Backend;
@@ -26,9 +27,9 @@ mod ls {
     use tower_lsp::{Client, LanguageServer};
     use tracing::debug;
 
-    use tree_sitter::{Language, Node, Parser, Point};
+    use tree_sitter::{Language, Parser};
 
-    use crate::ledger::{self, traverse, Ledger};
+    use crate::ledger::{self, Ledger};
 
     pub struct Backend {
         pub client: Client,
@@ -66,10 +67,10 @@ mod ls {
             }
         }
 
-        fn get_node_kind(&self, pos: Position) -> Option<NodeKind> {
+        fn get_node_kind(&self, uri: &Url, pos: Position) -> Option<NodeKind> {
             let ledger = self.ledger.write().unwrap();
             let mut kind = None;
-            ledger.traverse_ast(&mut |node| {
+            ledger.traverse_ast(uri, &mut |node| {
                 if pos.line as usize >= node.start_position().row
                     && pos.character as usize >= node.start_position().column
                     && pos.line as usize <= node.end_position().row
@@ -85,25 +86,31 @@ mod ls {
             kind
         }
 
-        fn account_completion(&self, pos: Position) -> Vec<CompletionItem> {
+        fn account_completion(&self, uri: &Url, pos: Position) -> Vec<CompletionItem> {
             let ledger = self.ledger.write().unwrap();
             let items = ledger
-                .get_accounts(pos)
+                .get_accounts(uri, pos)
                 .iter()
                 .map(|e| CompletionItem::new_simple(e.clone(), "Account".into()))
                 .collect::<Vec<CompletionItem>>();
             items
         }
 
-        fn payee_completion(&self, pos: Position) -> Vec<CompletionItem> {
+        fn payee_completion(&self, uri: &Url, pos: Position) -> Vec<CompletionItem> {
             let ledger = self.ledger.write().unwrap();
             let items = ledger
-                .get_payees(pos)
+                .get_payees(uri, pos)
                 .iter()
                 .map(|e| CompletionItem::new_simple(e.clone(), "Payee".into()))
                 .collect::<Vec<CompletionItem>>();
             items
         }
+
+        async fn publish_diagnostics(&self, diagnostics: Vec<Diagnostic>, uri: Url) {
+            self.client
+                .publish_diagnostics(uri, diagnostics, None)
+                .await;
+        }
     }
 
     #[tower_lsp::async_trait]
@@ -113,7 +120,7 @@ mod ls {
             Ok(InitializeResult {
                 capabilities: ServerCapabilities {
                     text_document_sync: Some(TextDocumentSyncCapability::Kind(
-                        TextDocumentSyncKind::FULL,
+                        TextDocumentSyncKind::INCREMENTAL,
                     )),
                     completion_provider: Some(CompletionOptions {
                         trigger_characters: Some(vec![":".into(), ".".into()]),
@@ -125,6 +132,12 @@ mod ls {
                             work_done_progress: None,
                         },
                     })),
+                    inlay_hint_provider: Some(OneOf::Left(true)),
+                    document_symbol_provider: Some(OneOf::Left(true)),
+                    folding_range_provider: Some(FoldingRangeProviderCapability::Simple(true)),
+                    references_provider: Some(OneOf::Left(true)),
+                    definition_provider: Some(OneOf::Left(true)),
+                    selection_range_provider: Some(SelectionRangeProviderCapability::Simple(true)),
                     ..Default::default()
                 },
                 ..Default::default()
@@ -138,18 +151,32 @@ mod ls {
         }
 
         async fn did_open(&self, params: DidOpenTextDocumentParams) {
-            let mut ledger = self.ledger.write().unwrap();
-            ledger.process_text(&params.text_document.text);
+            let uri = params.text_document.uri;
+            let diagnostics = {
+                let mut ledger = self.ledger.write().unwrap();
+                ledger.process_text(uri.clone(), &params.text_document.text);
+                ledger.compute_diagnostics(&uri)
+            };
+            self.publish_diagnostics(diagnostics, uri).await;
         }
 
         async fn did_change(&self, params: DidChangeTextDocumentParams) {
             debug!("did_change params: {:?}", params);
-            let mut ledger = self.ledger.write().unwrap();
-            ledger.process_text(&params.content_changes[0].text);
+            let uri = params.text_document.uri;
+            let diagnostics = {
+                let mut ledger = self.ledger.write().unwrap();
+                for change in &params.content_changes {
+                    ledger.apply_change(&uri, change);
+                }
+                ledger.compute_diagnostics(&uri)
+            };
+            self.publish_diagnostics(diagnostics, uri).await;
         }
 
         async fn did_close(&self, params: DidCloseTextDocumentParams) {
             debug!("Document close");
+            let mut ledger = self.ledger.write().unwrap();
+            ledger.close_document(&params.text_document.uri);
         }
 
         async fn completion(&self, params: CompletionParams) -> Result<Option<CompletionResponse>> {
@@ -159,15 +186,16 @@ mod ls {
                 params.text_document_position.position.character,
             );
             let pos = params.text_document_position.position;
-            match self.get_node_kind(pos) {
+            let uri = &params.text_document_position.text_document.uri;
+            match self.get_node_kind(uri, pos) {
                 Some(kind) => match kind {
                     NodeKind::Account => Ok(Some(CompletionResponse::List(CompletionList {
                         is_incomplete: false,
-                        items: self.account_completion(pos),
+                        items: self.account_completion(uri, pos),
                     }))),
                     NodeKind::Payee => Ok(Some(CompletionResponse::List(CompletionList {
                         is_incomplete: false,
-                        items: self.payee_completion(pos),
+                        items: self.payee_completion(uri, pos),
                     }))),
                 },
                 None => Ok(None),
@@ -202,40 +230,14 @@ mod ls {
                 params.position.line, params.position.character,
             );
             let pos = params.position;
+            let uri = &params.text_document.uri;
             let ledger = self.ledger.write().unwrap();
-            let node = ledger
-                .ast
-                .as_ref()
-                .unwrap()
-                .root_node()
-                .named_descendant_for_point_range(
-                    Point::new(pos.line as usize, pos.character as usize),
-                    Point::new(pos.line as usize, pos.character as usize),
-                )
-                .unwrap();
-            match NodeKind::try_from(node.kind().to_string()).ok() {
-                Some(kind) => match kind {
-                    NodeKind::Account | NodeKind::Payee => {
-                        Ok(Some(PrepareRenameResponse::RangeWithPlaceholder {
-                            range: Range::new(
-                                Position {
-                                    line: node.range().start_point.row as u32,
-                                    character: node.range().start_point.column as u32,
-                                },
-                                Position {
-                                    line: node.range().end_point.row as u32,
-                                    character: (node.range().end_point.column + 1) as u32,
-                                },
-                            ),
-                            placeholder: ledger.source
-                                [node.byte_range().start..node.byte_range().end]
-                                .to_string(),
-                        }))
-                    }
-                    _ => Ok(None),
-                },
-                None => Ok(None),
-            }
+            Ok(ledger
+                .find_rename_target(uri, pos)
+                .map(|(range, placeholder)| PrepareRenameResponse::RangeWithPlaceholder {
+                    range,
+                    placeholder,
+                }))
         }
 
         async fn rename(&self, params: RenameParams) -> Result<Option<WorkspaceEdit>> {
@@ -245,54 +247,92 @@ mod ls {
                 params.text_document_position.position.character,
             );
             let pos = params.text_document_position.position;
+            let uri = &params.text_document_position.text_document.uri;
             let ledger = self.ledger.write().unwrap();
-            let cur_node = ledger
-                .ast
-                .as_ref()
-                .unwrap()
-                .root_node()
-                .named_descendant_for_point_range(
-                    Point::new(pos.line as usize, pos.character as usize),
-                    Point::new(pos.line as usize, pos.character as usize),
-                )
-                .unwrap();
             let mut url_text_edit: HashMap<Url, Vec<TextEdit>> = HashMap::new();
-            let mut text_edit_vec: Vec<TextEdit> = vec![];
-            traverse(ledger.ast.as_ref().unwrap().root_node(), &mut |node| {
-                if node.kind() != cur_node.kind() {
-                    return;
-                }
+            for (occurrence_uri, range) in ledger.find_occurrences(uri, pos) {
+                url_text_edit
+                    .entry(occurrence_uri)
+                    .or_default()
+                    .push(TextEdit::new(range, params.new_name.clone()));
+            }
+            Ok(Some(WorkspaceEdit::new(url_text_edit)))
+        }
 
-                let text =
-                    ledger.source[node.byte_range().start..node.byte_range().end].to_string();
-                let cur_text = ledger.source
-                    [cur_node.byte_range().start..cur_node.byte_range().end]
-                    .to_string();
-                if cur_text != text {
-                    return;
-                }
+        async fn references(&self, params: ReferenceParams) -> Result<Option<Vec<Location>>> {
+            let pos = params.text_document_position.position;
+            let uri = &params.text_document_position.text_document.uri;
+            debug!("references at cursor: ({:?}, {:?})", pos.line, pos.character);
+            let ledger = self.ledger.write().unwrap();
+            let locations = ledger
+                .find_occurrences(uri, pos)
+                .into_iter()
+                .map(|(occurrence_uri, range)| Location::new(occurrence_uri, range))
+                .collect::<Vec<_>>();
+            Ok(if locations.is_empty() {
+                None
+            } else {
+                Some(locations)
+            })
+        }
 
-                let range = Range::new(
-                    Position {
-                        line: node.range().start_point.row as u32,
-                        character: node.range().start_point.column as u32,
-                    },
-                    Position {
-                        line: node.range().end_point.row as u32,
-                        character: (node.range().end_point.column + 1) as u32,
-                    },
-                );
-                text_edit_vec.push(TextEdit::new(range, params.new_name.clone()));
-            });
-            url_text_edit.insert(
-                params.text_document_position.text_document.uri,
-                // vec![TextEdit::new(
-                //     Range::new(Position::new(0, 0), Position::new(0, 9)),
-                //     params.new_name,
-                // )],
-                text_edit_vec,
+        async fn goto_definition(
+            &self,
+            params: GotoDefinitionParams,
+        ) -> Result<Option<GotoDefinitionResponse>> {
+            let pos = params.text_document_position_params.position;
+            let uri = &params.text_document_position_params.text_document.uri;
+            debug!(
+                "goto_definition at cursor: ({:?}, {:?})",
+                pos.line, pos.character
             );
-            Ok(Some(WorkspaceEdit::new(url_text_edit)))
+            let ledger = self.ledger.write().unwrap();
+            Ok(ledger
+                .find_definition(uri, pos)
+                .map(|(def_uri, range)| GotoDefinitionResponse::Scalar(Location::new(def_uri, range))))
+        }
+
+        async fn inlay_hint(&self, params: InlayHintParams) -> Result<Option<Vec<InlayHint>>> {
+            debug!("inlay_hint for range: {:?}", params.range);
+            let uri = &params.text_document.uri;
+            let ledger = self.ledger.write().unwrap();
+            let hints = ledger
+                .compute_inlay_hints(uri)
+                .into_iter()
+                .filter(|hint| hint.position >= params.range.start && hint.position <= params.range.end)
+                .collect();
+            Ok(Some(hints))
+        }
+
+        async fn document_symbol(
+            &self,
+            params: DocumentSymbolParams,
+        ) -> Result<Option<DocumentSymbolResponse>> {
+            debug!("document_symbol for {:?}", params.text_document.uri);
+            let ledger = self.ledger.write().unwrap();
+            Ok(Some(DocumentSymbolResponse::Nested(
+                ledger.compute_document_symbols(&params.text_document.uri),
+            )))
+        }
+
+        async fn folding_range(
+            &self,
+            params: FoldingRangeParams,
+        ) -> Result<Option<Vec<FoldingRange>>> {
+            debug!("folding_range for {:?}", params.text_document.uri);
+            let ledger = self.ledger.write().unwrap();
+            Ok(Some(ledger.compute_folding_ranges(&params.text_document.uri)))
+        }
+
+        async fn selection_range(
+            &self,
+            params: SelectionRangeParams,
+        ) -> Result<Option<Vec<SelectionRange>>> {
+            debug!("selection_range for {} position(s)", params.positions.len());
+            let ledger = self.ledger.write().unwrap();
+            Ok(Some(
+                ledger.compute_selection_ranges(&params.text_document.uri, &params.positions),
+            ))
         }
 
         async fn shutdown(&self) -> Result<()> {